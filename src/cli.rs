@@ -57,6 +57,126 @@ pub fn build<'a, 'b>() -> App<'a, 'b> {
                 .required(true),
         )
 
+        // retries on transient request failures
+        .arg(
+            Arg::with_name("retries")
+                .help("Number of times to retry a request on transient failure")
+                .long("retries")
+                .takes_value(true)
+                .default_value("3"),
+        )
+
+        // per-request timeout
+        .arg(
+            Arg::with_name("timeout")
+                .help("Per-request timeout in milliseconds, applied to S3 requests and to `--credentials chain` lookups")
+                .long("timeout")
+                .takes_value(true)
+                .default_value("30000"),
+        )
+
+        // custom S3-compatible endpoint
+        .arg(
+            Arg::with_name("endpoint")
+                .help("A custom endpoint to use, for S3-compatible stores")
+                .long("endpoint")
+                .takes_value(true)
+                .requires("region"),
+        )
+
+        // region to use against the endpoint
+        .arg(
+            Arg::with_name("region")
+                .help("The region to use, required when using a custom endpoint")
+                .long("region")
+                .takes_value(true),
+        )
+
+        // target content type
+        .arg(
+            Arg::with_name("content-type")
+                .help("A content type to set on the concatenated target(s)")
+                .long("content-type")
+                .takes_value(true),
+        )
+
+        // target ACL
+        .arg(
+            Arg::with_name("acl")
+                .help("A canned ACL to set on the concatenated target(s)")
+                .long("acl")
+                .takes_value(true),
+        )
+
+        // target content encoding
+        .arg(
+            Arg::with_name("content-encoding")
+                .help("A content encoding to set on the concatenated target(s)")
+                .long("content-encoding")
+                .takes_value(true),
+        )
+
+        // target cache control
+        .arg(
+            Arg::with_name("cache-control")
+                .help("A cache control header to set on the concatenated target(s)")
+                .long("cache-control")
+                .takes_value(true),
+        )
+
+        // inherit metadata from the first matched source
+        .arg(
+            Arg::with_name("inherit-metadata")
+                .help("Inherits content-type/content-encoding from the first matched source")
+                .long("inherit-metadata"),
+        )
+
+        // bounded worker pool size for part copies
+        .arg(
+            Arg::with_name("concurrency")
+                .help("Number of part copies to run concurrently")
+                .long("concurrency")
+                .takes_value(true)
+                .default_value("4"),
+        )
+
+        // credential provider selection
+        .arg(
+            Arg::with_name("credentials")
+                .help("The credential provider to authenticate with")
+                .long("credentials")
+                .takes_value(true)
+                .possible_values(&["chain", "static", "profile", "web-identity", "instance-metadata"])
+                .default_value("chain"),
+        )
+
+        // named profile, used with `--credentials profile`
+        .arg(
+            Arg::with_name("profile")
+                .help("A named profile to load credentials from")
+                .long("profile")
+                .takes_value(true),
+        )
+
+        // static access key, used with `--credentials static`
+        .arg(
+            Arg::with_name("access-key")
+                .help("An access key, used with `--credentials static`")
+                .long("access-key")
+                .takes_value(true)
+                .required_if("credentials", "static")
+                .requires("secret-key"),
+        )
+
+        // static secret key, used with `--credentials static`
+        .arg(
+            Arg::with_name("secret-key")
+                .help("A secret key, used with `--credentials static`")
+                .long("secret-key")
+                .takes_value(true)
+                .requires("access-key"),
+        )
+
         // settings required for parsing
         .settings(&[
             AppSettings::ArgRequiredElseHelp,
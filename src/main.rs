@@ -7,19 +7,69 @@
 //! (https://docs.aws.amazon.com/cli/latest/userguide/cli-environment.html).
 extern crate clap;
 extern crate env_logger;
+extern crate hyper_timeout;
+extern crate hyper_tls;
+extern crate indicatif;
 extern crate quick_xml;
 extern crate regex;
 extern crate rusoto_core;
 extern crate rusoto_s3;
 
+use hyper_timeout::TimeoutConnector;
+use hyper_tls::HttpsConnector;
+use indicatif::{ProgressBar, ProgressStyle};
 use regex::Regex;
-use rusoto_core::{credential::ChainProvider, region::Region, HttpClient};
+use rusoto_core::credential::{
+    ChainProvider, InstanceMetadataProvider, ProfileProvider, StaticProvider, WebIdentityProvider,
+};
+use rusoto_core::{region::Region, HttpClient};
 use rusoto_s3::*;
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Read;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 use std::time::Duration;
 
+/// Minimum part size enforced by S3 for every part except the last one, shared
+/// by both `UploadPartCopy` and `UploadPart`.
+const MIN_PART_SIZE: usize = 5_000_000;
+
+/// Target metadata sourced from CLI flags, threaded into the multipart upload
+/// on creation so the concatenated result isn't left at the `binary/octet-stream`
+/// / default-ACL fallback.
+struct TargetMetadata<'a> {
+    content_type: Option<&'a str>,
+    content_encoding: Option<&'a str>,
+    cache_control: Option<&'a str>,
+    acl: Option<&'a str>,
+    inherit: bool,
+}
+
+/// A source, queued during the listing walk, still waiting on its network
+/// round-trip before its part number can be finalized.
+///
+/// Kept as raw request data (rather than dispatching inline) so the slow part
+/// - a part copy or a `GetObject` fetch - can run concurrently, while part
+/// numbering and buffering stay single-threaded and in listing order.
+enum PendingOp {
+    Copy {
+        upload_id: String,
+        source_key: String,
+        size: i64,
+        request: UploadPartCopyRequest,
+    },
+    Small {
+        upload_id: String,
+        source_key: String,
+        size: i64,
+        full_target: String,
+        request: GetObjectRequest,
+    },
+}
+
 mod cli;
+mod retry;
 mod types;
 
 fn main() -> types::ConcatResult<()> {
@@ -49,29 +99,126 @@ fn main() -> types::ConcatResult<()> {
     // unpack the dry run argument
     let dryrun = args.is_present("dry");
 
+    // unpack the quiet argument, and build a progress bar to match; a dry run
+    // has nothing real to transfer, so it's suppressed the same as `--quiet`
+    let quiet = args.is_present("quiet");
+    let progress = if quiet || dryrun {
+        ProgressBar::hidden()
+    } else {
+        let bar = ProgressBar::new(0);
+
+        bar.set_style(
+            ProgressStyle::default_bar()
+                .template("{bytes}/{total_bytes} ({percent}%) [{bar:40.cyan/blue}] eta {eta}")
+                .progress_chars("=> "),
+        );
+
+        bar
+    };
+
     // unwrap and compile the source regex (unwrap should be safe)
     let source = Regex::new(&args.value_of("source").unwrap())?;
     let target = Cow::from(args.value_of("target").unwrap());
 
-    // create client options
-    let client = HttpClient::new()?;
-    let region = Region::default();
+    // unpack the retry/timeout arguments (clap guarantees these parse due to defaults)
+    let retries: u32 = args.value_of("retries").unwrap().parse().unwrap();
+    let timeout: u64 = args.value_of("timeout").unwrap().parse().unwrap();
+
+    // unpack the worker pool size for part copies
+    let concurrency: usize = args.value_of("concurrency").unwrap().parse().unwrap();
+
+    // unpack the target metadata overrides
+    let metadata = TargetMetadata {
+        content_type: args.value_of("content-type"),
+        content_encoding: args.value_of("content-encoding"),
+        cache_control: args.value_of("cache-control"),
+        acl: args.value_of("acl"),
+        inherit: args.is_present("inherit-metadata"),
+    };
+
+    // bound every request round-trip (not just credential fetches) by wrapping
+    // the connector `HttpClient::new()` would otherwise build unbounded; a large
+    // multipart completion can legitimately take minutes, so this is deliberately
+    // the same `--timeout` applied below, not a separate short request deadline
+    let mut connector = TimeoutConnector::new(HttpsConnector::new(4).expect("failed to build HTTPS connector"));
+    connector.set_connect_timeout(Some(Duration::from_millis(timeout)));
+    connector.set_read_timeout(Some(Duration::from_millis(timeout)));
+    connector.set_write_timeout(Some(Duration::from_millis(timeout)));
+
+    let client = HttpClient::new_with_connector(connector);
+
+    // fall back to the default provider chain unless a custom endpoint was given,
+    // which lets this tool target S3-compatible stores like MinIO or Wasabi
+    let region = match args.value_of("endpoint") {
+        Some(endpoint) => Region::Custom {
+            // presence is enforced by `requires` on the `endpoint` flag, since
+            // signing against the wrong region silently breaks some stores
+            name: args.value_of("region").expect("region should be required alongside endpoint").to_string(),
+            endpoint: endpoint.to_string(),
+        },
+        None => Region::default(),
+    };
+
+    // select the credential provider explicitly rather than always assuming a
+    // chain lookup; IAM role assumption (EC2/ECS metadata) and web-identity
+    // tokens both silently broke under the old hard-coded 500ms cap
+    let s3 = match args.value_of("credentials").unwrap() {
+        "static" => {
+            // presence is enforced by `requires` in the CLI definition
+            let access_key = args.value_of("access-key").unwrap();
+            let secret_key = args.value_of("secret-key").unwrap();
+            let provider = StaticProvider::new_minimal(access_key.to_string(), secret_key.to_string());
+
+            S3Client::new_with(client, provider, region)
+        }
+        "profile" => {
+            let mut provider = ProfileProvider::new()?;
+
+            if let Some(profile) = args.value_of("profile") {
+                provider.set_profile(profile);
+            }
+
+            S3Client::new_with(client, provider, region)
+        }
+        "web-identity" => {
+            // reads AWS_WEB_IDENTITY_TOKEN_FILE / AWS_ROLE_ARN from the environment
+            let provider = WebIdentityProvider::from_k8s_env();
 
-    // create provided with timeout
-    let mut chain = ChainProvider::new();
-    chain.set_timeout(Duration::from_millis(500));
+            S3Client::new_with(client, provider, region)
+        }
+        "instance-metadata" => {
+            let provider = InstanceMetadataProvider::new();
 
-    // construct new S3 client
-    let s3 = S3Client::new_with(client, chain, region);
+            S3Client::new_with(client, provider, region)
+        }
+        _ => {
+            // the configured timeout, rather than a hard-coded 500ms cap, since
+            // completing a large multipart upload can take minutes while
+            // listing objects should be quick
+            let mut chain = ChainProvider::new();
+            chain.set_timeout(Duration::from_millis(timeout));
+
+            S3Client::new_with(client, chain, region)
+        }
+    };
 
     // sources and target -> upload mappings
     let mut sources: HashMap<String, HashSet<String>> = HashMap::new();
     let mut targets: HashMap<String, String> = HashMap::new();
 
+    // per-upload part counters, and in-memory buffers for sub-5MB sources that
+    // can't go through `UploadPartCopy` directly; each buffer is tagged with the
+    // part number reserved for it when it was first opened, so its eventual part
+    // keeps the same position in listing order that its first source had
+    let mut parts: HashMap<String, i64> = HashMap::new();
+    let mut buffers: HashMap<String, (i64, Vec<u8>)> = HashMap::new();
+
     // construct uploads - this is separate to allow easy
     // handling of errors being returned (and cleanup)
     let result = construct_uploads(
         dryrun,
+        retries,
+        concurrency,
         &s3,
         bucket.clone(),
         prefix,
@@ -79,8 +226,15 @@ fn main() -> types::ConcatResult<()> {
         source,
         &mut sources,
         &mut targets,
+        &mut parts,
+        &mut buffers,
+        &metadata,
+        &progress,
     );
 
+    // wrap up the progress bar now that copying itself is done, success or not
+    progress.finish_and_clear();
+
     // dry doesn't post-process
     if dryrun {
         return Ok(());
@@ -115,8 +269,10 @@ fn main() -> types::ConcatResult<()> {
             ..ListPartsRequest::default()
         };
 
-        // carry out the request for the parts list
-        let parts_result = s3.list_parts(parts).sync();
+        // carry out the request for the parts list, retrying on transient failure
+        let parts_result = retry::with_retry(retries, || {
+            s3.list_parts(parts.clone()).sync().map_err(Into::into)
+        });
 
         // attempt to list the pending parts
         if let Err(err) = parts_result {
@@ -161,7 +317,13 @@ fn main() -> types::ConcatResult<()> {
         };
 
         // attempt to complete each request, abort on fail (can't short circut)
-        if let Err(_) = s3.complete_multipart_upload(complete).sync() {
+        let completion = retry::with_retry(retries, || {
+            s3.complete_multipart_upload(complete.clone())
+                .sync()
+                .map_err(Into::into)
+        });
+
+        if completion.is_err() {
             // remove the upload sources
             sources.remove(&key);
 
@@ -189,8 +351,12 @@ fn main() -> types::ConcatResult<()> {
                 ..DeleteObjectRequest::default()
             };
 
-            // attemp to remove the objects from S3
-            if let Err(_) = s3.delete_object(delete).sync() {
+            // attemp to remove the objects from S3, retrying on transient failure
+            let removal = retry::with_retry(retries, || {
+                s3.delete_object(delete.clone()).sync().map_err(Into::into)
+            });
+
+            if removal.is_err() {
                 eprintln!("Unable to remove {}", key);
             }
         }
@@ -206,6 +372,8 @@ fn main() -> types::ConcatResult<()> {
 /// function for error handling (this allows us to use ? in this function).
 fn construct_uploads<'a>(
     dry: bool,
+    retries: u32,
+    concurrency: usize,
     s3: &S3Client,
     bucket: Cow<'a, str>,
     prefix: Cow<'a, str>,
@@ -213,6 +381,10 @@ fn construct_uploads<'a>(
     pattern: Regex,
     sources: &mut HashMap<String, HashSet<String>>,
     targets: &mut HashMap<String, String>,
+    parts: &mut HashMap<String, i64>,
+    buffers: &mut HashMap<String, (i64, Vec<u8>)>,
+    metadata: &TargetMetadata,
+    progress: &ProgressBar,
 ) -> types::ConcatResult<()> {
     // iteration token
     let mut token = None;
@@ -238,6 +410,12 @@ fn construct_uploads<'a>(
             continue;
         }
 
+        // raw operations queued up this page, in listing order; the network
+        // round-trips (GetObject fetches and part copies) are dispatched
+        // concurrently below, but part-number assignment and buffering stay
+        // single-threaded so ordering within a target is still preserved
+        let mut ops: Vec<PendingOp> = Vec::new();
+
         // iterate all objects
         for entry in response.contents.unwrap() {
             // unwrap the source key
@@ -248,10 +426,10 @@ fn construct_uploads<'a>(
                 continue;
             }
 
-            // AWS doesn't let us concat < 5MB
-            if entry.size.unwrap() < 5000000 {
-                return Err(format!("Unable to concat files below 5MB: {}", key).into());
-            }
+            // whether this source is big enough to copy as its own part, or
+            // needs to be buffered and coalesced with its neighbours instead
+            let size = entry.size.unwrap();
+            let small_file = (size as usize) < MIN_PART_SIZE;
 
             // format the source path, as well as the target
             let part_source = format!("{}/{}", bucket, key);
@@ -272,12 +450,41 @@ fn construct_uploads<'a>(
                 continue;
             }
 
+            // grow the total as pages arrive, rather than a separate listing pass
+            progress.inc_length(size as u64);
+
             // ensure we have an upload identifier
             if !targets.contains_key(&full_target) {
+                // explicit flags win; fall back to the first source's own
+                // content-type/encoding when `--inherit-metadata` is set
+                let mut content_type = metadata.content_type.map(String::from);
+                let mut content_encoding = metadata.content_encoding.map(String::from);
+
+                if metadata.inherit && (content_type.is_none() || content_encoding.is_none()) {
+                    let head_request = HeadObjectRequest {
+                        bucket: bucket.to_string(),
+                        key: key.clone(),
+                        ..HeadObjectRequest::default()
+                    };
+
+                    let head = retry::with_retry(retries, || {
+                        s3.head_object(head_request.clone())
+                            .sync()
+                            .map_err(Into::into)
+                    })?;
+
+                    content_type = content_type.or(head.content_type);
+                    content_encoding = content_encoding.or(head.content_encoding);
+                }
+
                 // initialize the upload request as needed
                 let creation = CreateMultipartUploadRequest {
                     bucket: bucket.to_string(),
                     key: full_target.to_string(),
+                    content_type,
+                    content_encoding,
+                    cache_control: metadata.cache_control.map(String::from),
+                    acl: metadata.acl.map(String::from),
                     ..CreateMultipartUploadRequest::default()
                 };
 
@@ -293,26 +500,190 @@ fn construct_uploads<'a>(
             // retrieve the upload identifier for the target
             let upload_id = targets
                 .get(&full_target)
-                .expect("upload identifier should always be mapped");
+                .expect("upload identifier should always be mapped")
+                .clone();
+
+            if small_file {
+                // too small for `UploadPartCopy` - queue it for a `GetObject`
+                // fetch instead; the bytes get buffered and flushed as a real
+                // `UploadPart` once there's enough to meet S3's 5MB minimum
+                // (the final part is flushed once listing ends)
+                let get_request = GetObjectRequest {
+                    bucket: bucket.to_string(),
+                    key: key.clone(),
+                    ..GetObjectRequest::default()
+                };
 
-            // retrieve the sources list for the upload_id
-            let sources = sources.get_mut(&*upload_id).unwrap();
+                ops.push(PendingOp::Small {
+                    upload_id,
+                    source_key: key,
+                    size,
+                    full_target,
+                    request: get_request,
+                });
+            } else {
+                // the actual part number is assigned once this page's buffered
+                // sources are merged in, so copy-parts and buffer-parts land in
+                // listing order; `0` here is just a placeholder
+                let copy_request = UploadPartCopyRequest {
+                    bucket: bucket.to_string(),
+                    copy_source: part_source,
+                    part_number: 0,
+                    key: full_target,
+                    upload_id: upload_id.clone(),
+                    ..UploadPartCopyRequest::default()
+                };
 
-            // create the copy request for the existing key
-            let copy_request = UploadPartCopyRequest {
-                bucket: bucket.to_string(),
-                copy_source: part_source,
-                part_number: (sources.len() + 1) as i64,
-                key: full_target,
-                upload_id: upload_id.to_string(),
-                ..UploadPartCopyRequest::default()
-            };
+                ops.push(PendingOp::Copy {
+                    upload_id,
+                    source_key: key,
+                    size,
+                    request: copy_request,
+                });
+            }
+        }
 
-            // carry out the request for the part copy
-            s3.upload_part_copy(copy_request).sync()?;
+        // fetch every buffered small source concurrently; the bytes are merged
+        // into their targets' buffers below, single-threaded, in listing order
+        let fetch_jobs = ops
+            .iter()
+            .filter_map(|op| match op {
+                PendingOp::Small { request, .. } => {
+                    let s3 = s3.clone();
+                    let request = request.clone();
+
+                    Some(move || -> types::ConcatResult<Vec<u8>> {
+                        let object = retry::with_retry(retries, || {
+                            s3.get_object(request.clone()).sync().map_err(Into::into)
+                        })?;
+
+                        let mut bytes = Vec::new();
+                        object
+                            .body
+                            .expect("object body should exist")
+                            .into_blocking_read()
+                            .read_to_end(&mut bytes)?;
+
+                        Ok(bytes)
+                    })
+                }
+                PendingOp::Copy { .. } => None,
+            }).collect();
 
-            // push the source for removal
-            sources.insert(key);
+        let mut fetches = run_concurrently(concurrency, fetch_jobs).into_iter();
+
+        // part copies finalized below, dispatched concurrently once every
+        // object in this page has had its part number assigned
+        let mut copy_jobs: Vec<(String, String, i64, UploadPartCopyRequest)> = Vec::new();
+
+        for op in ops {
+            match op {
+                PendingOp::Small {
+                    upload_id,
+                    source_key,
+                    size,
+                    full_target,
+                    ..
+                } => {
+                    let bytes = fetches
+                        .next()
+                        .expect("one fetch result per buffered source")?;
+
+                    // coalescing only ever covers a *consecutive* run of sources
+                    // (small ones, plus at most one large one that closes the
+                    // buffer), so the part number is reserved up front, when the buffer is
+                    // opened by the first source to land in it - not at flush
+                    // time - keeping it in the listing-order slot it would have
+                    // occupied as a lone part
+                    let buffer = &mut buffers
+                        .entry(upload_id.clone())
+                        .or_insert_with(|| (next_part_number(parts, &upload_id), Vec::new()))
+                        .1;
+                    buffer.extend_from_slice(&bytes);
+
+                    if buffer.len() >= MIN_PART_SIZE {
+                        let (part_number, body) = buffers.remove(&upload_id).unwrap();
+                        flush_buffer(retries, s3, &bucket, &full_target, &upload_id, part_number, body)?;
+                    }
+
+                    // push the source for removal, and count its bytes as transferred
+                    sources.get_mut(&upload_id).unwrap().insert(source_key);
+                    progress.inc(size as u64);
+                }
+                PendingOp::Copy {
+                    upload_id,
+                    source_key,
+                    size,
+                    mut request,
+                } => {
+                    // S3 only waives the 5MB minimum for the very last part of an
+                    // upload, so a pending buffer under that threshold can't just
+                    // be flushed here - that would emit an illegal undersized,
+                    // non-final part. Instead, pull this source down and append
+                    // it to the buffer in place of copying it: since this source
+                    // alone is >= MIN_PART_SIZE, the combined buffer clears the
+                    // minimum and can be flushed immediately under the part
+                    // number already reserved for it, preserving listing order.
+                    let pending = buffers
+                        .remove(&upload_id)
+                        .filter(|(_, body)| !body.is_empty());
+
+                    if let Some((part_number, mut body)) = pending {
+                        let get_request = GetObjectRequest {
+                            bucket: bucket.to_string(),
+                            key: source_key.clone(),
+                            ..GetObjectRequest::default()
+                        };
+
+                        let object = retry::with_retry(retries, || {
+                            s3.get_object(get_request.clone()).sync().map_err(Into::into)
+                        })?;
+
+                        object
+                            .body
+                            .expect("object body should exist")
+                            .into_blocking_read()
+                            .read_to_end(&mut body)?;
+
+                        flush_buffer(retries, s3, &bucket, &request.key, &upload_id, part_number, body)?;
+
+                        sources.get_mut(&upload_id).unwrap().insert(source_key);
+                        progress.inc(size as u64);
+                    } else {
+                        request.part_number = next_part_number(parts, &upload_id);
+                        copy_jobs.push((upload_id, source_key, size, request));
+                    }
+                }
+            }
+        }
+
+        // dispatch this page's part copies across a bounded worker pool,
+        // retrying each individually on transient failure
+        let outcomes = run_concurrently(
+            concurrency,
+            copy_jobs
+                .into_iter()
+                .map(|(upload_id, source_key, size, request)| {
+                    let s3 = s3.clone();
+
+                    move || -> types::ConcatResult<(String, String, i64)> {
+                        retry::with_retry(retries, || {
+                            s3.upload_part_copy(request.clone())
+                                .sync()
+                                .map_err(Into::into)
+                        })?;
+
+                        Ok((upload_id, source_key, size))
+                    }
+                }).collect(),
+        );
+
+        // record every completed copy so it's still cleaned up on success; the
+        // first failure is returned so `main` can abort the in-flight targets
+        for outcome in outcomes {
+            let (upload_id, source_key, size) = outcome?;
+            sources.get_mut(&upload_id).unwrap().insert(source_key);
+            progress.inc(size as u64);
         }
 
         // break if there's no way to continue
@@ -324,9 +695,118 @@ fn construct_uploads<'a>(
         token = response.next_continuation_token;
     }
 
+    // flush any remainder that never reached the 5MB threshold; S3 waives the
+    // minimum part size requirement for the last part of a multipart upload
+    for (full_target, upload_id) in targets.iter() {
+        if let Some((part_number, body)) = buffers.remove(upload_id) {
+            flush_buffer(retries, s3, &bucket, full_target, upload_id, part_number, body)?;
+        }
+    }
+
     Ok(())
 }
 
+/// Runs `jobs` across a bounded pool of worker threads, `concurrency` at a time.
+///
+/// Jobs are pulled from a shared queue by a fixed pool of worker threads, so a
+/// worker that finishes early immediately picks up the next job rather than
+/// waiting on the rest of a fixed-size batch; results are returned in the same
+/// order as `jobs`. This keeps the blocking `.sync()` calls used throughout
+/// this crate, rather than pulling in an async runtime just for the copy phase.
+fn run_concurrently<T, F>(concurrency: usize, jobs: Vec<F>) -> Vec<types::ConcatResult<T>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> types::ConcatResult<T> + Send + 'static,
+{
+    let total = jobs.len();
+    let workers = concurrency.max(1).min(total.max(1));
+
+    let queue = Arc::new(Mutex::new(jobs.into_iter().enumerate().collect::<VecDeque<_>>()));
+    let (sender, receiver) = mpsc::channel();
+
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let sender = sender.clone();
+
+            thread::spawn(move || loop {
+                let next = queue.lock().expect("job queue poisoned").pop_front();
+
+                match next {
+                    Some((index, job)) => sender
+                        .send((index, job()))
+                        .expect("result channel should still be open"),
+                    None => break,
+                }
+            })
+        }).collect();
+
+    // drop our own sender so the channel closes once every worker is done
+    drop(sender);
+
+    let mut results: Vec<Option<types::ConcatResult<T>>> = (0..total).map(|_| None).collect();
+    for (index, result) in receiver {
+        results[index] = Some(result);
+    }
+
+    for handle in handles {
+        handle.join().expect("worker thread panicked");
+    }
+
+    results
+        .into_iter()
+        .map(|result| result.expect("every job should report exactly one result"))
+        .collect()
+}
+
+/// Uploads a buffered chunk of coalesced small sources as a single real part.
+///
+/// `part_number` is whatever was reserved for this buffer when it was opened,
+/// not assigned here, so the part lands in the same listing-order slot its
+/// first source would have taken on its own. Issues an `UploadPart` request,
+/// retrying on transient failure. A no-op for an empty buffer, which happens
+/// when every source coalesced into it was zero bytes long.
+fn flush_buffer(
+    retries: u32,
+    s3: &S3Client,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    part_number: i64,
+    body: Vec<u8>,
+) -> types::ConcatResult<()> {
+    if body.is_empty() {
+        return Ok(());
+    }
+
+    retry::with_retry(retries, || {
+        let upload_request = UploadPartRequest {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            upload_id: upload_id.to_string(),
+            part_number,
+            body: Some(body.clone().into()),
+            ..UploadPartRequest::default()
+        };
+
+        s3.upload_part(upload_request).sync().map_err(Into::into)
+    })?;
+
+    Ok(())
+}
+
+/// Returns the next sequential part number for `upload_id`, starting at 1.
+///
+/// Copy-based and buffered parts share this counter so that, regardless of
+/// which path produced a given part, part numbers stay in the order sources
+/// were encountered while walking the bucket. A number reserved for a buffer
+/// that stays empty is never uploaded, leaving a gap in the sequence.
+fn next_part_number(parts: &mut HashMap<String, i64>, upload_id: &str) -> i64 {
+    let counter = parts.entry(upload_id.to_string()).or_insert(0);
+    *counter += 1;
+    *counter
+}
+
 /// Aborts a multipart request in S3 by upload_id.
 ///
 /// This can be used to abort a failed upload request, due to either the inability
@@ -0,0 +1,117 @@
+//! Retry helpers for transient S3 request failures.
+use types::ConcatResult;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Base delay used for the exponential backoff calculation.
+const BASE_DELAY_MILLIS: u64 = 100;
+
+/// Upper bound on the total time spent sleeping between retries.
+const MAX_TOTAL_BACKOFF_MILLIS: u64 = 30_000;
+
+/// Retries `op` up to `retries` times on a retriable error.
+///
+/// Backoff grows exponentially as `base_delay * 2^attempt`, plus a small
+/// amount of jitter, and each sleep is capped so the cumulative backoff stops
+/// growing at [`MAX_TOTAL_BACKOFF_MILLIS`] - once the cap is hit, every
+/// remaining retry still runs, just with a 1ms sleep between attempts rather
+/// than a longer one. A non-retriable error (see `ConcatError::is_retriable`)
+/// or the final attempt is returned immediately.
+pub fn with_retry<T, F>(retries: u32, mut op: F) -> ConcatResult<T>
+where
+    F: FnMut() -> ConcatResult<T>,
+{
+    let mut attempt = 0;
+    let mut total_backoff_millis = 0;
+
+    loop {
+        let err = match op() {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+
+        if attempt >= retries || !err.is_retriable() {
+            return Err(err);
+        }
+
+        let delay_millis = (BASE_DELAY_MILLIS.saturating_mul(1 << attempt) + jitter_millis())
+            .min(MAX_TOTAL_BACKOFF_MILLIS.saturating_sub(total_backoff_millis))
+            .max(1);
+
+        thread::sleep(Duration::from_millis(delay_millis));
+
+        total_backoff_millis = (total_backoff_millis + delay_millis).min(MAX_TOTAL_BACKOFF_MILLIS);
+        attempt += 1;
+    }
+}
+
+/// Returns a small pseudo-random jitter, in milliseconds, to avoid retry storms.
+fn jitter_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| u64::from(duration.subsec_nanos()) % (BASE_DELAY_MILLIS / 2))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::with_retry;
+    use types::ConcatError;
+
+    #[test]
+    fn succeeds_without_retrying() {
+        let mut calls = 0;
+
+        let result = with_retry(3, || {
+            calls += 1;
+            Ok::<_, ConcatError>(42)
+        });
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn retries_retriable_errors_until_success() {
+        let mut calls = 0;
+
+        let result = with_retry(3, || {
+            calls += 1;
+
+            if calls < 3 {
+                Err(ConcatError::from("request timed out"))
+            } else {
+                Ok(42)
+            }
+        });
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn gives_up_after_exhausting_retries() {
+        let mut calls = 0;
+
+        let result = with_retry(2, || {
+            calls += 1;
+            Err::<(), _>(ConcatError::from("request timed out"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn does_not_retry_non_retriable_errors() {
+        let mut calls = 0;
+
+        let result = with_retry(3, || {
+            calls += 1;
+            Err::<(), _>(ConcatError::from("InvalidAccessKeyId"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
@@ -32,6 +32,33 @@ impl Display for ConcatError {
     }
 }
 
+impl ConcatError {
+    /// Determines whether this error represents a transient failure worth retrying.
+    ///
+    /// `ConcatError` collapses every Rusoto variant down to a rendered message, so
+    /// retriability is classified by inspecting it for markers of timeouts, dropped
+    /// connections, and S3 throttling (503 SlowDown) rather than matching on a type.
+    /// Markers are kept specific (e.g. `"connection reset"` rather than a bare
+    /// `"connection"`, `"internal error"`/`"503"` rather than a bare `"500"`) so a
+    /// message that merely happens to embed a broader substring isn't misclassified.
+    /// Auth failures and other 4xx responses fall through and are not retried.
+    pub fn is_retriable(&self) -> bool {
+        let message = self.0.to_lowercase();
+
+        message.contains("timed out")
+            || message.contains("timeout")
+            || message.contains("connection reset")
+            || message.contains("connection refused")
+            || message.contains("connection aborted")
+            || message.contains("slow down")
+            || message.contains("slowdown")
+            || message.contains("throttl")
+            || message.contains("please reduce your request rate")
+            || message.contains("503")
+            || message.contains("internal error")
+    }
+}
+
 /// Macro to implement `From` for provided types.
 macro_rules! derive_from {
     ($type:ty) => {
@@ -49,6 +76,7 @@ derive_from!(io::Error);
 derive_from!(SetLoggerError);
 derive_from!(regex::Error);
 derive_from!(request::TlsError);
+derive_from!(rusoto_core::credential::CredentialsError);
 derive_from!(time::SystemTimeError);
 derive_from!(String);
 
@@ -102,9 +130,12 @@ derive_from_rusoto!(rusoto_s3::AbortMultipartUploadError);
 derive_from_rusoto!(rusoto_s3::CompleteMultipartUploadError);
 derive_from_rusoto!(rusoto_s3::CreateMultipartUploadError);
 derive_from_rusoto!(rusoto_s3::DeleteObjectError);
+derive_from_rusoto!(rusoto_s3::GetObjectError);
+derive_from_rusoto!(rusoto_s3::HeadObjectError);
 derive_from_rusoto!(rusoto_s3::ListObjectsV2Error);
 derive_from_rusoto!(rusoto_s3::ListPartsError);
 derive_from_rusoto!(rusoto_s3::UploadPartCopyError);
+derive_from_rusoto!(rusoto_s3::UploadPartError);
 
 #[cfg(test)]
 mod tests {
@@ -156,4 +187,24 @@ mod tests {
 
         assert_eq!(convert.0, message);
     }
+
+    #[test]
+    fn retriable_errors_are_detected() {
+        assert!(ConcatError::from("request timed out").is_retriable());
+        assert!(ConcatError::from("connection reset by peer").is_retriable());
+        assert!(ConcatError::from("Please reduce your request rate.").is_retriable());
+        assert!(ConcatError::from("SlowDown: slow down").is_retriable());
+        assert!(ConcatError::from("InternalError: We encountered an internal error").is_retriable());
+    }
+
+    #[test]
+    fn non_retriable_errors_are_not_detected() {
+        assert!(!ConcatError::from("InvalidAccessKeyId").is_retriable());
+        assert!(!ConcatError::from("NoSuchKey: The specified key does not exist").is_retriable());
+
+        // these merely embed a broad substring ("500", "connection") without
+        // actually describing a transient 5xx/connection failure
+        assert!(!ConcatError::from("InvalidArgument: limit must be between 1 and 500").is_retriable());
+        assert!(!ConcatError::from("InvalidArgument: connection string is malformed").is_retriable());
+    }
 }